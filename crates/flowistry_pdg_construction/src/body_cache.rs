@@ -1,24 +1,29 @@
 use std::{
     cell::RefCell,
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
     time::{Duration, Instant},
 };
 
 use flowistry::mir::FlowistryInput;
 
 use polonius_engine::FactTypes;
-use rustc_borrowck::consumers::{ConsumerOptions, RustcFacts};
-use rustc_hash::FxHashMap;
-use rustc_hir::{
-    def_id::{CrateNum, DefId, DefIndex, LocalDefId, LOCAL_CRATE},
-    intravisit::{self},
+use rustc_borrowck::consumers::{BodyWithBorrowckFacts, ConsumerOptions, RustcFacts};
+use rustc_data_structures::{
+    fingerprint::Fingerprint,
+    stable_hasher::{HashStable, StableHasher},
+    sync::{par_for_each_in, Lock},
 };
+use rustc_hash::FxHashMap;
+use rustc_hir::def_id::{CrateNum, DefId, DefIndex, LocalDefId, StableCrateId, LOCAL_CRATE};
 use rustc_macros::{Decodable, Encodable, TyDecodable, TyEncodable};
 use rustc_middle::{
-    hir::nested_filter::OnlyBodies,
     mir::{Body, ClearCrossCrate, StatementKind},
     ty::TyCtxt,
+    util::Providers,
 };
+use rustc_session::Session;
 
 use rustc_type_ir::RegionVid;
 use rustc_utils::cache::Cache;
@@ -27,41 +32,210 @@ use crate::encoder::{decode_from_file, encode_to_file};
 
 /// A mir [`Body`] and all the additional borrow checking facts that our
 /// points-to analysis needs.
-#[derive(TyDecodable, TyEncodable, Debug)]
+#[derive(Clone, TyDecodable, TyEncodable, Debug)]
 pub struct CachedBody<'tcx> {
     body: Body<'tcx>,
     input_facts: FlowistryFacts,
+    /// Fingerprint of the *input* to [`Self::retrieve`] (the owner's
+    /// [`DefPathHash`](rustc_hir::def_id::DefPathHash) combined with a stable
+    /// hash of its pre-borrowck MIR), cheap to recompute on a later
+    /// compilation. [`dump_mir_and_borrowck_facts`] compares this against the
+    /// previous manifest to tell, without re-running borrowck-fact
+    /// extraction, whether an entry can be reused as-is.
+    input_fingerprint: Fingerprint,
 }
 
 impl<'tcx> CachedBody<'tcx> {
     /// Retrieve a body and the necessary facts for a local item.
     ///
-    /// Ensure this is called early enough in the compiler
-    /// (like `after_expansion`) so that the body has not been stolen yet.
-    fn retrieve(tcx: TyCtxt<'tcx>, local_def_id: LocalDefId) -> Self {
-        let mut body_with_facts = rustc_borrowck::consumers::get_body_with_borrowck_facts(
-            tcx,
-            local_def_id,
-            ConsumerOptions::PoloniusInputFacts,
+    /// `collect_extra_facts` controls whether the full set of Polonius loan
+    /// facts is retained alongside `subset_base` in the returned value -- see
+    /// [`BodyCache::collect_extra_facts`]. The underlying facts are always
+    /// extracted in full by [`stash_borrowck_facts`] (computing them is just
+    /// a linear copy of vectors borrowck already produced, not additional
+    /// analysis work); this function trims them back out of its own return
+    /// value when the caller didn't ask for them, so two `retrieve` calls
+    /// for the same `local_def_id` with different flags each get exactly
+    /// what they asked for.
+    ///
+    /// Forces the `mir_borrowck` query for `local_def_id` (via
+    /// [`rustc_middle::ty::TyCtxt::ensure`]) and then looks up the body
+    /// [`stash_borrowck_facts`] already processed for it in
+    /// [`processed_bodies`]. `mir_borrowck` is a regular rustc query, so
+    /// `ensure` only actually runs the provider (and therefore only
+    /// populates the cache) the first time it's demanded for a given
+    /// `local_def_id` -- every later demand, possibly from a different
+    /// worker thread, is a query-cache hit that runs nothing, so the entry
+    /// must already be there.
+    fn retrieve(tcx: TyCtxt<'tcx>, local_def_id: LocalDefId, collect_extra_facts: bool) -> Self {
+        tcx.ensure().mir_borrowck(local_def_id);
+
+        let cached = processed_bodies()
+            .lock()
+            .get(&local_def_id)
+            .cloned()
+            .unwrap_or_else(|| {
+                panic!(
+                    "no processed body for {local_def_id:?}; \
+                     was `override_queries` installed in `Callbacks::config`?"
+                )
+            });
+
+        // `stash_borrowck_facts` always populates `extra`, regardless of any
+        // particular caller's `collect_extra_facts`, precisely so two
+        // `retrieve` calls for the same `local_def_id` with different flags
+        // (e.g. a `dump_mir_and_borrowck_facts` run and a separately
+        // configured `BodyCache` asking for the same id) don't silently
+        // return each other's stale view. Guard that invariant here instead
+        // of letting a future change to `stash_borrowck_facts` regress it
+        // into a silent `None` for callers that asked for the facts.
+        debug_assert!(
+            cached.input_facts.extra.is_some(),
+            "processed body for {local_def_id:?} is missing Polonius loan facts; \
+             `stash_borrowck_facts` must always populate them"
         );
 
-        clean_undecodable_data_from_body(&mut body_with_facts.body);
-
-        Self {
-            body: body_with_facts.body,
-            input_facts: FlowistryFacts {
-                subset_base: body_with_facts
-                    .input_facts
-                    .expect("polonius input must exist")
-                    .subset_base
-                    .iter()
-                    .map(|&(v1, v2, _)| (v1.into(), v2.into()))
-                    .collect(),
-            },
+        // SAFETY: inverse of the erasure in `stash_borrowck_facts`. We only
+        // ever read an entry back within the same compilation session that
+        // produced it, so reinstating `'tcx` here is sound.
+        let mut cached: CachedBody<'tcx> = unsafe { std::mem::transmute(cached) };
+        if !collect_extra_facts {
+            cached.input_facts.extra = None;
         }
+        cached
     }
 }
 
+/// Compute a fingerprint for the *input* to [`CachedBody::retrieve`], i.e.
+/// something cheap enough to recompute on every compilation so that we don't
+/// have to pay for borrowck-fact extraction just to find out an entry is
+/// unchanged. Combines the owner's `DefPathHash` (which changes if the item
+/// is renamed or moved) with a stable hash of its pre-borrowck MIR (which
+/// changes with any HIR/MIR-relevant edit to the body).
+fn input_fingerprint<'tcx>(tcx: TyCtxt<'tcx>, local_def_id: LocalDefId) -> Fingerprint {
+    tcx.with_stable_hashing_context(|mut hcx| {
+        let mut hasher = StableHasher::new();
+        tcx.def_path_hash(local_def_id.to_def_id())
+            .hash_stable(&mut hcx, &mut hasher);
+        tcx.mir_built(local_def_id)
+            .borrow()
+            .hash_stable(&mut hcx, &mut hasher);
+        hasher.finish()
+    })
+}
+
+/// Bodies processed by [`stash_borrowck_facts`] as the `mir_borrowck` query
+/// runs for each body owner, keyed by the owner's `LocalDefId` and read by
+/// [`CachedBody::retrieve`].
+///
+/// Process-wide, not thread-local: `tcx.ensure().mir_borrowck(id)` only
+/// actually *runs* the overridden provider (and therefore only populates
+/// this map) on whichever thread first demands the query for a given `id`
+/// -- every later demand, on any thread, is a query-cache hit that runs
+/// nothing. Most bodies are already borrow-checked during rustc's normal
+/// analysis pass, which itself runs across a worker pool under `-Z
+/// threads`, so the thread that later calls `tcx.ensure()` from
+/// [`CachedBody::retrieve`] (e.g. under [`dump_mir_and_borrowck_facts`]'s
+/// `par_for_each_in`) is often not the one that originally populated this
+/// map; a thread-local bridge would find nothing there.
+///
+/// The `'static` lifetime is an erasure of the actual `'tcx` of the
+/// compilation session that produced the entry; we never read an entry
+/// back outside the session that wrote it, so reinstating `'tcx` in
+/// [`CachedBody::retrieve`] is sound.
+fn processed_bodies() -> &'static Lock<FxHashMap<LocalDefId, CachedBody<'static>>> {
+    static PROCESSED_BODIES: OnceLock<Lock<FxHashMap<LocalDefId, CachedBody<'static>>>> =
+        OnceLock::new();
+    PROCESSED_BODIES.get_or_init(|| Lock::new(FxHashMap::default()))
+}
+
+/// Install our override of the `mir_borrowck` query. Call this from the
+/// driver's `Callbacks::config`.
+///
+/// Overriding the query, rather than calling
+/// [`rustc_borrowck::consumers::get_body_with_borrowck_facts`] from an
+/// item-like walk after the fact, guarantees we see every body that ever
+/// gets borrow-checked -- including const/static initializers, inline
+/// consts and promoteds, which a `visit_fn`-only walk misses -- and we see
+/// it at the one point in the compiler where it cannot yet have been
+/// stolen.
+pub fn override_queries(_session: &Session, providers: &mut Providers) {
+    providers.mir_borrowck = stash_borrowck_facts;
+}
+
+/// The `mir_borrowck` provider installed by [`override_queries`]: runs the
+/// real analysis with Polonius facts enabled, processes the result into a
+/// [`CachedBody`] -- always with the full set of Polonius loan facts
+/// populated, since extracting them here is just a linear copy of vectors
+/// borrowck already produced -- and stores it in [`processed_bodies`] for
+/// [`CachedBody::retrieve`] to pick up, then forwards to rustc's own
+/// `mir_borrowck` so the rest of the compiler still sees normal borrowck
+/// diagnostics.
+///
+/// Doing the processing here, rather than handing the raw
+/// [`BodyWithBorrowckFacts`] off for [`CachedBody::retrieve`] to process
+/// later, is what lets [`processed_bodies`] be a plain process-wide lock
+/// instead of a thread-local: `BodyWithBorrowckFacts` holds `Rc`s that can't
+/// safely cross threads, but the [`CachedBody`] built from it here can.
+fn stash_borrowck_facts<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: LocalDefId,
+) -> rustc_middle::query::queries::mir_borrowck::ProvidedValue<'tcx> {
+    let mut body_with_facts = rustc_borrowck::consumers::get_body_with_borrowck_facts(
+        tcx,
+        def_id,
+        ConsumerOptions::PoloniusInputFacts,
+    );
+
+    clean_undecodable_data_from_body(&mut body_with_facts.body);
+
+    let raw_facts = body_with_facts
+        .input_facts
+        .expect("polonius input must exist");
+
+    let input_facts = FlowistryFacts {
+        subset_base: raw_facts
+            .subset_base
+            .iter()
+            .map(|&(v1, v2, _)| (v1.into(), v2.into()))
+            .collect(),
+        extra: Some(ExtraFacts {
+            loan_issued_at: raw_facts
+                .loan_issued_at
+                .iter()
+                .map(|&(origin, loan, point)| (origin.into(), loan, point.into()))
+                .collect(),
+            loan_killed_at: raw_facts
+                .loan_killed_at
+                .iter()
+                .map(|&(loan, point)| (loan, point.into()))
+                .collect(),
+            loan_invalidated_at: raw_facts
+                .loan_invalidated_at
+                .iter()
+                .map(|&(point, loan)| (point.into(), loan))
+                .collect(),
+            cfg_edge: raw_facts
+                .cfg_edge
+                .iter()
+                .map(|&(from, to)| (from.into(), to.into()))
+                .collect(),
+        }),
+    };
+
+    let cached = CachedBody {
+        body: body_with_facts.body,
+        input_facts,
+        input_fingerprint: input_fingerprint(tcx, def_id),
+    };
+
+    // SAFETY: see the comment on `processed_bodies`.
+    let erased: CachedBody<'static> = unsafe { std::mem::transmute(cached) };
+    processed_bodies().lock().insert(def_id, erased);
+
+    rustc_borrowck::mir_borrowck(tcx, def_id)
+}
+
 impl<'tcx> FlowistryInput<'tcx, 'tcx> for &'tcx CachedBody<'tcx> {
     fn body(self) -> &'tcx Body<'tcx> {
         &self.body
@@ -72,16 +246,83 @@ impl<'tcx> FlowistryInput<'tcx, 'tcx> for &'tcx CachedBody<'tcx> {
     }
 }
 
+impl<'tcx> CachedBody<'tcx> {
+    /// The loans issued at each point, together with the region they were
+    /// issued into. `None` unless the extra facts were requested: for a local
+    /// body via [`BodyCache::collect_extra_facts`], for a body loaded from
+    /// an artifact via the `collect_extra_facts` argument of the
+    /// [`dump_mir_and_borrowck_facts`] call that wrote it.
+    pub fn loan_issued_at(
+        &self,
+    ) -> Option<impl Iterator<Item = (RegionVid, Loan, LocationIndex)> + '_> {
+        Some(self.input_facts.extra.as_ref()?.loan_issued_at.iter().copied())
+    }
+
+    /// The points at which each loan is killed (goes out of scope).
+    pub fn loan_killed_at(&self) -> Option<impl Iterator<Item = (Loan, LocationIndex)> + '_> {
+        Some(self.input_facts.extra.as_ref()?.loan_killed_at.iter().copied())
+    }
+
+    /// The points at which each loan is invalidated by a conflicting access.
+    pub fn loan_invalidated_at(
+        &self,
+    ) -> Option<impl Iterator<Item = (LocationIndex, Loan)> + '_> {
+        Some(
+            self.input_facts
+                .extra
+                .as_ref()?
+                .loan_invalidated_at
+                .iter()
+                .copied(),
+        )
+    }
+
+    /// The control-flow graph, as an edge relation between points.
+    pub fn cfg_edge(&self) -> Option<impl Iterator<Item = (LocationIndex, LocationIndex)> + '_> {
+        Some(self.input_facts.extra.as_ref()?.cfg_edge.iter().copied())
+    }
+}
+
 /// The subset of borrowcheck facts that the points-to analysis (flowistry)
 /// needs.
-#[derive(Debug, Encodable, Decodable)]
+#[derive(Debug, Clone, Encodable, Decodable)]
 pub struct FlowistryFacts {
     pub subset_base: Vec<(RegionVid, RegionVid)>,
+    /// The remaining Polonius input facts, present when they were requested
+    /// (see [`CachedBody::loan_issued_at`]).
+    pub extra: Option<ExtraFacts>,
 }
 
-pub type LocationIndex = <RustcFacts as FactTypes>::Point;
+/// Polonius loan facts beyond `subset_base`, for loan-sensitive flow queries
+/// (e.g. "is this borrow still live at this point").
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct ExtraFacts {
+    pub loan_issued_at: Vec<(RegionVid, Loan, LocationIndex)>,
+    pub loan_killed_at: Vec<(Loan, LocationIndex)>,
+    pub loan_invalidated_at: Vec<(LocationIndex, Loan)>,
+    pub cfg_edge: Vec<(LocationIndex, LocationIndex)>,
+}
 
-type BodyMap<'tcx> = FxHashMap<DefIndex, CachedBody<'tcx>>;
+pub type LocationIndex = <RustcFacts as FactTypes>::Point;
+pub type Loan = <RustcFacts as FactTypes>::Loan;
+
+/// A crate's manifest: enough per-entry metadata to decide whether a
+/// [`CachedBody`] is still valid, without decoding it. The body itself lives
+/// in its own file (see [`body_file_path`]), named after its `DefIndex`, so
+/// [`BodyCache::get`] only ever decodes the one body it was asked for.
+type Manifest = FxHashMap<DefIndex, ManifestEntry>;
+
+/// Metadata about one entry in a crate's [`Manifest`].
+#[derive(Debug, Encodable, Decodable, Clone, Copy)]
+struct ManifestEntry {
+    input_fingerprint: Fingerprint,
+    /// Whether the body this entry describes was dumped with the
+    /// `collect_extra_facts` argument of [`dump_mir_and_borrowck_facts`]
+    /// set. Kept here, rather than
+    /// only on the body itself, so that [`dump_mir_and_borrowck_facts`] can
+    /// decide an entry is stale without decoding it.
+    has_extra_facts: bool,
+}
 
 /// Allows loading bodies from previosly written artifacts.
 ///
@@ -89,21 +330,40 @@ type BodyMap<'tcx> = FxHashMap<DefIndex, CachedBody<'tcx>>;
 /// bodies it returns or risk UB.
 pub struct BodyCache<'tcx> {
     tcx: TyCtxt<'tcx>,
-    cache: Cache<CrateNum, BodyMap<'tcx>>,
+    /// Maps a crate to its manifest and the directory holding its per-body
+    /// files, resolved and validated once per crate.
+    manifests: Cache<CrateNum, (Manifest, PathBuf)>,
+    remote_cache: Cache<(CrateNum, DefIndex), CachedBody<'tcx>>,
     local_cache: Cache<DefIndex, CachedBody<'tcx>>,
     timer: RefCell<Duration>,
+    collect_extra_facts: bool,
 }
 
 impl<'tcx> BodyCache<'tcx> {
     pub fn new(tcx: TyCtxt<'tcx>) -> Self {
         Self {
             tcx,
-            cache: Default::default(),
+            manifests: Default::default(),
+            remote_cache: Default::default(),
             local_cache: Default::default(),
             timer: RefCell::new(Duration::ZERO),
+            collect_extra_facts: false,
         }
     }
 
+    /// Also retain the full Polonius loan facts (`loan_issued_at`,
+    /// `loan_killed_at`, `loan_invalidated_at`, `cfg_edge`), not just
+    /// `subset_base`, for any body this cache retrieves locally.
+    ///
+    /// This setting does not change what [`dump_mir_and_borrowck_facts`]
+    /// writes to disk, which is controlled by that function's own
+    /// `collect_extra_facts` argument, and it has no effect on bodies loaded
+    /// from another crate's artifact.
+    pub fn collect_extra_facts(mut self, yes: bool) -> Self {
+        self.collect_extra_facts = yes;
+        self
+    }
+
     pub fn timer(&self) -> Duration {
         *self.timer.borrow()
     }
@@ -112,19 +372,36 @@ impl<'tcx> BodyCache<'tcx> {
     ///
     /// Returns `None` if the policy forbids loading from this crate.
     pub fn get(&self, key: DefId) -> &'tcx CachedBody<'tcx> {
-        println!("{:?}", key);
         let body = if let Some(local) = key.as_local() {
             self.local_cache.get(&local.local_def_index, |_| {
                 let start = Instant::now();
-                let res = CachedBody::retrieve(self.tcx, local);
+                let res = CachedBody::retrieve(self.tcx, local, self.collect_extra_facts);
                 *self.timer.borrow_mut() += start.elapsed();
                 res
             })
         } else {
-            self.cache
-                .get(&key.krate, |_| load_body_and_facts(self.tcx, key.krate))
-                .get(&key.index)
-                .expect("Invariant broken, body for this is should exist")
+            let (manifest, dir) = self.manifests.get(&key.krate, |_| {
+                load_remote_manifest(self.tcx, key.krate)
+                    .unwrap_or_else(|msg| self.tcx.sess.fatal(msg))
+            });
+
+            if !manifest.contains_key(&key.index) {
+                self.tcx.sess.fatal(format!(
+                    "no cached body recorded for {key:?} in manifest at {}; rebuild the dependency",
+                    dir.display()
+                ));
+            }
+
+            self.remote_cache.get(&(key.krate, key.index), |_| {
+                let path = body_file_path(dir, key.index);
+                decode_from_file(self.tcx, &path).unwrap_or_else(|err| {
+                    self.tcx.sess.fatal(format!(
+                        "failed to decode cached body for {key:?} from {} ({err}); delete the \
+                         artifact and rebuild the dependency",
+                        path.display()
+                    ))
+                })
+            })
         };
 
         // SAFETY: Theoretically this struct may not outlive the body, but
@@ -137,12 +414,6 @@ impl<'tcx> BodyCache<'tcx> {
     }
 }
 
-/// A visitor to collect all bodies in the crate and write them to disk.
-struct DumpingVisitor<'tcx> {
-    tcx: TyCtxt<'tcx>,
-    targets: Vec<LocalDefId>,
-}
-
 /// Some data in a [Body] is not cross-crate compatible. Usually because it
 /// involves storing a [LocalDefId]. This function makes sure to sanitize those
 /// out.
@@ -162,66 +433,323 @@ fn clean_undecodable_data_from_body(body: &mut Body) {
     }
 }
 
-impl<'tcx> intravisit::Visitor<'tcx> for DumpingVisitor<'tcx> {
-    type NestedFilter = OnlyBodies;
-    fn nested_visit_map(&mut self) -> Self::Map {
-        self.tcx.hir()
-    }
-
-    fn visit_fn(
-        &mut self,
-        function_kind: intravisit::FnKind<'tcx>,
-        function_declaration: &'tcx rustc_hir::FnDecl<'tcx>,
-        body_id: rustc_hir::BodyId,
-        _: rustc_span::Span,
-        local_def_id: rustc_hir::def_id::LocalDefId,
-    ) {
-        self.targets.push(local_def_id);
-
-        intravisit::walk_fn(
-            self,
-            function_kind,
-            function_declaration,
-            body_id,
-            local_def_id,
-        )
+/// Magic number identifying a `.bwbf` manifest, so that [`validate_version`]
+/// can recognize and reject a decoded file that isn't one of ours.
+const ARTIFACT_MAGIC: u32 = 0xB0D1_CAC3;
+
+/// Bump this whenever [`ArtifactManifest`], [`ManifestEntry`] or
+/// [`CachedBody`]'s on-disk layout changes, so that a stale manifest (or the
+/// body files it indexes) from a previous version is rejected instead of
+/// (mis-)decoded.
+const ARTIFACT_VERSION: u32 = 1;
+
+/// Precedes the encoded [`Manifest`] in every `.bwbf` file. Self-describing:
+/// besides the magic/version pair, it records which crate and which rustc
+/// toolchain produced the artifact, so [`ArtifactHeader::validate`] can tell
+/// a stale-toolchain artifact and a stale-crate artifact apart instead of
+/// both surfacing as an opaque decode failure.
+#[derive(Encodable, Decodable)]
+struct ArtifactHeader {
+    magic: u32,
+    version: u32,
+    /// The `rustc` toolchain ([`rustc_session::Session::cfg_version`]) that
+    /// produced this artifact.
+    rustc_version: String,
+    /// Stable identity of the crate this artifact was dumped for.
+    source_crate: StableCrateId,
+}
+
+impl ArtifactHeader {
+    fn current(tcx: TyCtxt<'_>) -> Self {
+        Self {
+            magic: ARTIFACT_MAGIC,
+            version: ARTIFACT_VERSION,
+            rustc_version: tcx.sess.cfg_version.to_owned(),
+            source_crate: tcx.stable_crate_id(LOCAL_CRATE),
+        }
+    }
+
+    /// Validate this header against the current compilation, returning the
+    /// specific reason it's unusable (rather than a bare `bool`) so callers
+    /// can report a diagnostic that tells the user what to actually do.
+    fn validate(
+        &self,
+        path: &Path,
+        current_rustc_version: &str,
+        expected_crate: StableCrateId,
+    ) -> Result<(), ArtifactLoadError> {
+        validate_version(
+            path,
+            self.magic,
+            self.version,
+            &self.rustc_version,
+            current_rustc_version,
+        )?;
+        if self.source_crate != expected_crate {
+            return Err(ArtifactLoadError::WrongCrate {
+                path: path.to_owned(),
+                expected: expected_crate,
+                found: self.source_crate,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The magic-number, format-version and toolchain checks out of
+/// [`ArtifactHeader::validate`], factored out so they can be unit tested
+/// without a live [`TyCtxt`] -- unlike the crate-identity check `validate`
+/// also does, these only ever compare plain data.
+fn validate_version(
+    path: &Path,
+    magic: u32,
+    version: u32,
+    rustc_version: &str,
+    current_rustc_version: &str,
+) -> Result<(), ArtifactLoadError> {
+    if magic != ARTIFACT_MAGIC {
+        return Err(ArtifactLoadError::Corrupt {
+            path: path.to_owned(),
+            detail: "bad magic number".to_owned(),
+        });
+    }
+    if version != ARTIFACT_VERSION {
+        return Err(ArtifactLoadError::FormatMismatch {
+            path: path.to_owned(),
+            artifact_version: version,
+            current_version: ARTIFACT_VERSION,
+        });
+    }
+    if rustc_version != current_rustc_version {
+        return Err(ArtifactLoadError::ToolchainMismatch {
+            path: path.to_owned(),
+            artifact_rustc_version: rustc_version.to_owned(),
+            current_rustc_version: current_rustc_version.to_owned(),
+        });
     }
+    Ok(())
 }
 
-/// A complete visit over the local crate items, collecting all bodies and
-/// calculating the necessary borrowcheck facts to store for later points-to
-/// analysis.
+/// Why loading a previously-written artifact failed, so the caller can
+/// report a diagnostic that tells the user what to do about it, instead of
+/// panicking with an opaque decode error.
+enum ArtifactLoadError {
+    /// The file doesn't decode as one of our artifacts at all, e.g. it was
+    /// truncated by a crash mid-write.
+    Corrupt { path: PathBuf, detail: String },
+    /// The magic is ours, but the on-disk format version predates (or
+    /// postdates) [`ARTIFACT_VERSION`].
+    FormatMismatch {
+        path: PathBuf,
+        artifact_version: u32,
+        current_version: u32,
+    },
+    /// The format matches, but the artifact was produced by a different
+    /// `rustc` toolchain than the one running this compilation, so it may
+    /// encode types or MIR shapes this session's rustc can't decode.
+    ToolchainMismatch {
+        path: PathBuf,
+        artifact_rustc_version: String,
+        current_rustc_version: String,
+    },
+    /// The artifact decoded and validated fine, but was dumped for a
+    /// different crate than the one this path was resolved for (e.g. a
+    /// stale file left behind after a crate was renamed).
+    WrongCrate {
+        path: PathBuf,
+        expected: StableCrateId,
+        found: StableCrateId,
+    },
+}
+
+impl ArtifactLoadError {
+    /// Render this as an actionable message, suitable for a compiler
+    /// diagnostic telling the user to rebuild the offending dependency.
+    fn message(&self) -> String {
+        match self {
+            Self::Corrupt { path, detail } => format!(
+                "paralegal artifact at {} is corrupt ({detail}); delete it and rebuild the dependency",
+                path.display()
+            ),
+            Self::FormatMismatch {
+                path,
+                artifact_version,
+                current_version,
+            } => format!(
+                "paralegal artifact at {} was written by format version {artifact_version}, which is \
+                 incompatible with this compilation's format version {current_version}; rebuild the \
+                 dependency",
+                path.display()
+            ),
+            Self::ToolchainMismatch {
+                path,
+                artifact_rustc_version,
+                current_rustc_version,
+            } => format!(
+                "paralegal artifact at {} was written by rustc {artifact_rustc_version}, which is \
+                 incompatible with this compilation's rustc {current_rustc_version}; rebuild the \
+                 dependency",
+                path.display()
+            ),
+            Self::WrongCrate {
+                path,
+                expected,
+                found,
+            } => format!(
+                "paralegal artifact at {} was written for a different crate ({found:?}) than the one \
+                 expected here ({expected:?}); delete it and rebuild the dependency",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// The on-disk representation of a `.bwbf` file: a small header followed by
+/// an index of every body in the crate, keyed by `DefIndex`. The bodies
+/// themselves live one-per-file in the sibling directory returned by
+/// [`bodies_dir`], so decoding this manifest never requires decoding any of
+/// them.
+#[derive(Encodable, Decodable)]
+struct ArtifactManifest {
+    header: ArtifactHeader,
+    entries: Manifest,
+}
+
+/// The directory holding a crate's per-body files, derived from the path of
+/// its manifest (e.g. `libfoo-<hash>.bwbf` -> `libfoo-<hash>.bwbf.d`).
+fn bodies_dir(manifest_path: &Path) -> PathBuf {
+    let mut name = manifest_path.as_os_str().to_owned();
+    name.push(".d");
+    PathBuf::from(name)
+}
+
+/// The file holding a single body's encoded [`CachedBody`], inside the
+/// crate's body directory.
+fn body_file_path(dir: &Path, index: DefIndex) -> PathBuf {
+    dir.join(format!("{}.body", index.as_u32()))
+}
+
+/// Load the manifest this compilation previously wrote for its own crate, if
+/// any, so entries whose [`ManifestEntry::input_fingerprint`] is unchanged
+/// can be reused as-is instead of re-derived and re-encoded.
 ///
-/// Ensure this gets called early in the compiler before the unoptimized mir
-/// bodies are stolen.
-pub fn dump_mir_and_borrowck_facts<'tcx>(tcx: TyCtxt<'tcx>) -> (Duration, Duration) {
-    let mut vis = DumpingVisitor {
-        tcx,
-        targets: vec![],
-    };
-    tcx.hir().visit_all_item_likes_in_crate(&mut vis);
-
-    let tc_start = Instant::now();
-    let bodies: BodyMap<'tcx> = vis
-        .targets
-        .iter()
-        .map(|local_def_id| {
-            let to_write = CachedBody::retrieve(tcx, *local_def_id);
-            println!("{:?}, {:?}", local_def_id, local_def_id.local_def_index);
-
-            (local_def_id.local_def_index, to_write)
-        })
-        .collect();
-    let tc_time = tc_start.elapsed();
+/// Returns an empty manifest if there is no previous one or it is unreadable
+/// (e.g. from an incompatible version) -- either way every body is
+/// (re)computed this time, we just lose the incremental speedup.
+fn load_previous_manifest(tcx: TyCtxt<'_>, path: &Path) -> Manifest {
+    if !path.exists() {
+        return Default::default();
+    }
+    let manifest: Result<ArtifactManifest, _> = decode_from_file(tcx, path);
+    match manifest {
+        Ok(manifest)
+            if manifest
+                .header
+                .validate(path, &tcx.sess.cfg_version, tcx.stable_crate_id(LOCAL_CRATE))
+                .is_ok() =>
+        {
+            manifest.entries
+        }
+        _ => Default::default(),
+    }
+}
+
+/// Whether the previous manifest's entry for a body (if any) can be reused
+/// as-is by [`dump_mir_and_borrowck_facts`] instead of re-derived via
+/// [`CachedBody::retrieve`]: it must have been dumped with the same
+/// `collect_extra_facts` setting, its [`input_fingerprint`]
+/// must still match, and the body file it names must still be on disk.
+fn is_reusable(
+    previous_entry: Option<&ManifestEntry>,
+    collect_extra_facts: bool,
+    fingerprint: Fingerprint,
+    body_path: &Path,
+) -> bool {
+    previous_entry.is_some_and(|old| {
+        old.has_extra_facts == collect_extra_facts
+            && old.input_fingerprint == fingerprint
+            && body_path.exists()
+    })
+}
+
+/// Collect every local body owner -- not just free and associated
+/// functions, but const/static initializers, inline consts, closures and
+/// generators too -- and dump the borrowck facts of each to store for later
+/// points-to analysis.
+///
+/// Reuses entries from the previous manifest whose
+/// [`ManifestEntry::input_fingerprint`] is unchanged: such a body is neither
+/// re-derived via [`CachedBody::retrieve`] nor re-encoded, it is simply left
+/// on disk and its manifest entry is carried over.
+///
+/// Targets that do need (re-)retrieving are spread over rustc's parallel
+/// facilities ([`par_for_each_in`], a no-op fallback to sequential iteration
+/// when the compiler wasn't built with `-Z threads`): `retrieve` and
+/// `encode_to_file` for each body run independently of the others, so this
+/// is embarrassingly parallel.
+///
+/// Call this from `after_analysis`. [`override_queries`] must already have
+/// been installed in `Callbacks::config` for the `tcx.ensure().mir_borrowck`
+/// calls inside [`CachedBody::retrieve`] to find anything in
+/// [`processed_bodies`].
+pub fn dump_mir_and_borrowck_facts<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    collect_extra_facts: bool,
+) -> (Duration, Duration) {
+    let targets: Vec<LocalDefId> = tcx.hir().body_owners().collect();
+
+    let manifest_path = intermediate_out_dir(tcx, INTERMEDIATE_ARTIFACT_EXT);
+    let previous = load_previous_manifest(tcx, &manifest_path);
+
+    let dir = bodies_dir(&manifest_path);
+    fs::create_dir_all(&dir)
+        .unwrap_or_else(|err| panic!("failed to create body cache directory {}: {err}", dir.display()));
+
+    // Aggregated across workers rather than wall-clock, since with
+    // retrieval running in parallel wall-clock time no longer reflects how
+    // much borrowck-fact-extraction work actually happened.
+    let tc_time = Lock::new(Duration::ZERO);
+    let entries = Lock::new(Manifest::default());
+
+    par_for_each_in(targets.as_slice(), |local_def_id| {
+        let local_def_id = *local_def_id;
+        let index = local_def_id.local_def_index;
+        let fingerprint = input_fingerprint(tcx, local_def_id);
+        let body_path = body_file_path(&dir, index);
+
+        let reusable = is_reusable(previous.get(&index), collect_extra_facts, fingerprint, &body_path);
+
+        if !reusable {
+            let start = Instant::now();
+            let cached = CachedBody::retrieve(tcx, local_def_id, collect_extra_facts);
+            *tc_time.lock() += start.elapsed();
+            encode_to_file(tcx, &body_path, &cached);
+        }
+
+        entries.lock().insert(
+            index,
+            ManifestEntry {
+                input_fingerprint: fingerprint,
+                has_extra_facts: collect_extra_facts,
+            },
+        );
+    });
+
+    let tc_time = tc_time.into_inner();
+    let entries = entries.into_inner();
+
     let dump_time = Instant::now();
-    let path = intermediate_out_dir(tcx, INTERMEDIATE_ARTIFACT_EXT);
-    encode_to_file(tcx, path, &bodies);
+    let manifest = ArtifactManifest {
+        header: ArtifactHeader::current(tcx),
+        entries,
+    };
+    encode_to_file(tcx, manifest_path, &manifest);
     (tc_time, dump_time.elapsed())
 }
 
 const INTERMEDIATE_ARTIFACT_EXT: &str = "bwbf";
 
-/// Get the path where artifacts from this crate would be stored. Unlike
+/// Get the path where this crate's artifact manifest would be stored. Unlike
 /// [`TyCtxt::crate_extern_paths`] this function does not crash when supplied
 /// with [`LOCAL_CRATE`].
 pub fn local_or_remote_paths(krate: CrateNum, tcx: TyCtxt, ext: &str) -> Vec<PathBuf> {
@@ -237,19 +765,55 @@ pub fn local_or_remote_paths(krate: CrateNum, tcx: TyCtxt, ext: &str) -> Vec<Pat
     }
 }
 
-/// Try to load a [`CachedBody`] for this id.
-fn load_body_and_facts(tcx: TyCtxt<'_>, krate: CrateNum) -> BodyMap<'_> {
-    let paths = local_or_remote_paths(krate, tcx, INTERMEDIATE_ARTIFACT_EXT);
-    for path in &paths {
-        if !path.exists() {
+/// Load the manifest for a (possibly remote) crate, so [`BodyCache::get`]
+/// can look up a single entry without decoding any of the bodies it names.
+///
+/// Returns a ready-to-report diagnostic message, rather than panicking, if
+/// no usable manifest is found: either none of the candidate paths exist
+/// (the dependency wasn't compiled with paralegal instrumentation), or the
+/// most recent attempt that did exist was corrupt, from an incompatible
+/// toolchain, or dumped for a different crate.
+fn load_remote_manifest(tcx: TyCtxt<'_>, krate: CrateNum) -> Result<(Manifest, PathBuf), String> {
+    let manifest_paths = local_or_remote_paths(krate, tcx, INTERMEDIATE_ARTIFACT_EXT);
+    let expected_crate = tcx.stable_crate_id(krate);
+    let mut last_error = None;
+
+    for manifest_path in &manifest_paths {
+        if !manifest_path.exists() {
             continue;
         }
 
-        let data = decode_from_file(tcx, path).unwrap();
-        return data;
+        let manifest: Result<ArtifactManifest, _> = decode_from_file(tcx, manifest_path);
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                last_error = Some(ArtifactLoadError::Corrupt {
+                    path: manifest_path.clone(),
+                    detail: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        match manifest
+            .header
+            .validate(manifest_path, &tcx.sess.cfg_version, expected_crate)
+        {
+            Ok(()) => return Ok((manifest.entries, bodies_dir(manifest_path))),
+            Err(err) => {
+                last_error = Some(err);
+                continue;
+            }
+        }
     }
 
-    panic!("No facts for {krate:?} found at any path tried: {paths:?}");
+    Err(match last_error {
+        Some(err) => err.message(),
+        None => format!(
+            "no paralegal artifact found for {krate:?} at any of {manifest_paths:?}; was this \
+             dependency compiled with paralegal instrumentation enabled?"
+        ),
+    })
 }
 
 /// Create the name of the file in which to store intermediate artifacts.
@@ -278,3 +842,108 @@ pub fn intermediate_out_dir(tcx: TyCtxt, ext: &str) -> PathBuf {
 
     dir.join(file.as_ref())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file we know exists, whatever directory the tests are run from.
+    const EXISTING_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/body_cache.rs");
+
+    fn entry(fingerprint: Fingerprint, has_extra_facts: bool) -> ManifestEntry {
+        ManifestEntry {
+            input_fingerprint: fingerprint,
+            has_extra_facts,
+        }
+    }
+
+    #[test]
+    fn reusable_when_fingerprint_and_flag_match_and_file_exists() {
+        // Stand in for "the body file is on disk" with a file we know exists.
+        let existing_path = Path::new(EXISTING_FILE);
+        let old = entry(Fingerprint::ZERO, false);
+        assert!(is_reusable(Some(&old), false, Fingerprint::ZERO, existing_path));
+    }
+
+    #[test]
+    fn not_reusable_when_fingerprint_changed() {
+        let existing_path = Path::new(EXISTING_FILE);
+        let old = entry(Fingerprint::ZERO, false);
+        assert!(!is_reusable(
+            Some(&old),
+            false,
+            Fingerprint::new(1, 1),
+            existing_path
+        ));
+    }
+
+    #[test]
+    fn not_reusable_when_extra_facts_flag_changed() {
+        let existing_path = Path::new(EXISTING_FILE);
+        let old = entry(Fingerprint::ZERO, false);
+        assert!(!is_reusable(Some(&old), true, Fingerprint::ZERO, existing_path));
+    }
+
+    #[test]
+    fn not_reusable_when_body_file_missing() {
+        let old = entry(Fingerprint::ZERO, false);
+        let missing_path = Path::new("/nonexistent/paralegal-body-cache-test.body");
+        assert!(!is_reusable(Some(&old), false, Fingerprint::ZERO, missing_path));
+    }
+
+    #[test]
+    fn not_reusable_without_a_previous_entry() {
+        assert!(!is_reusable(None, false, Fingerprint::ZERO, Path::new(EXISTING_FILE)));
+    }
+
+    #[test]
+    fn validate_version_accepts_a_matching_header() {
+        assert!(validate_version(
+            Path::new("artifact.bwbf"),
+            ARTIFACT_MAGIC,
+            ARTIFACT_VERSION,
+            "rustc 1.0.0",
+            "rustc 1.0.0",
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn validate_version_rejects_bad_magic() {
+        let err = validate_version(
+            Path::new("artifact.bwbf"),
+            0xdead_beef,
+            ARTIFACT_VERSION,
+            "rustc 1.0.0",
+            "rustc 1.0.0",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ArtifactLoadError::Corrupt { .. }));
+    }
+
+    #[test]
+    fn validate_version_rejects_a_format_mismatch() {
+        let err = validate_version(
+            Path::new("artifact.bwbf"),
+            ARTIFACT_MAGIC,
+            ARTIFACT_VERSION + 1,
+            "rustc 1.0.0",
+            "rustc 1.0.0",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ArtifactLoadError::FormatMismatch { .. }));
+    }
+
+    #[test]
+    fn validate_version_rejects_a_toolchain_mismatch() {
+        let err = validate_version(
+            Path::new("artifact.bwbf"),
+            ARTIFACT_MAGIC,
+            ARTIFACT_VERSION,
+            "rustc 1.0.0",
+            "rustc 1.1.0",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ArtifactLoadError::ToolchainMismatch { .. }));
+    }
+}